@@ -1,150 +1,174 @@
 use libc::{c_char, c_int, size_t};
+use std::collections::HashSet;
 use std::ffi::{CStr, CString};
 use std::ptr;
-use tiktoken_rs::{cl100k_base, CoreBPE};
+use tiktoken_rs::{cl100k_base, o200k_base, p50k_base, r50k_base, CoreBPE};
+use unicode_segmentation::UnicodeSegmentation;
 
-static mut TOKENIZER: Option<CoreBPE> = None;
-static INIT: std::sync::Once = std::sync::Once::new();
+/// An opaque handle wrapping a loaded BPE tokenizer for a single encoding.
+///
+/// Obtain one via `tokenizer_create` and release it with `tokenizer_destroy`.
+/// Holding several handles at once lets a caller count tokens for more than
+/// one model/encoding in the same process.
+pub struct Tokenizer {
+    bpe: CoreBPE,
+}
+
+fn bpe_for_encoding(name: &str) -> Option<CoreBPE> {
+    match name {
+        "cl100k_base" => cl100k_base().ok(),
+        "o200k_base" => o200k_base().ok(),
+        "p50k_base" => p50k_base().ok(),
+        "r50k_base" => r50k_base().ok(),
+        _ => None,
+    }
+}
 
-/// Initialize the tokenizer with cl100k_base encoding
-/// Returns 0 on success, -1 on failure
+/// Create a tokenizer for the given encoding name.
+///
+/// Supported names are `cl100k_base` (GPT-3.5/4), `o200k_base` (GPT-4o),
+/// `p50k_base`, and `r50k_base`. Returns a null pointer if the name is
+/// unrecognized or the vocabulary fails to load. The returned pointer must
+/// be freed with `tokenizer_destroy`.
 #[no_mangle]
-pub extern "C" fn tokenizer_initialize() -> c_int {
-    INIT.call_once(|| {
-        // Initialize with cl100k_base encoding (GPT-4/GPT-3.5-turbo)
-        // This will use the bundled vocabulary data
-        match cl100k_base() {
-            Ok(bpe) => unsafe {
-                TOKENIZER = Some(bpe);
-            },
-            Err(e) => {
-                eprintln!("Failed to initialize tokenizer: {}", e);
-            }
+pub extern "C" fn tokenizer_create(encoding_name: *const c_char) -> *mut Tokenizer {
+    if encoding_name.is_null() {
+        return ptr::null_mut();
+    }
+
+    let c_str = unsafe { CStr::from_ptr(encoding_name) };
+    let name = match c_str.to_str() {
+        Ok(s) => s,
+        Err(_) => return ptr::null_mut(),
+    };
+
+    match bpe_for_encoding(name) {
+        Some(bpe) => Box::into_raw(Box::new(Tokenizer { bpe })),
+        None => {
+            #[cfg(debug_assertions)]
+            eprintln!("Unknown tokenizer encoding: {}", name);
+            ptr::null_mut()
         }
-    });
-    
-    unsafe {
-        if TOKENIZER.is_some() {
-            0
-        } else {
-            -1
+    }
+}
+
+/// Destroy a tokenizer previously returned by `tokenizer_create`.
+/// Passing a null pointer is a no-op.
+#[no_mangle]
+pub extern "C" fn tokenizer_destroy(tokenizer: *mut Tokenizer) {
+    if !tokenizer.is_null() {
+        unsafe {
+            let _ = Box::from_raw(tokenizer);
         }
     }
 }
 
-/// Count tokens in the given text
-/// Returns the token count, or -1 on error
+/// Count tokens in the given text using the given tokenizer.
+/// Returns the token count, or -1 on error.
 #[no_mangle]
-pub extern "C" fn tokenizer_count_tokens(text: *const c_char) -> c_int {
-    if text.is_null() {
-        return 0;
+pub extern "C" fn tokenizer_count_tokens(tokenizer: *const Tokenizer, text: *const c_char) -> c_int {
+    if tokenizer.is_null() || text.is_null() {
+        return -1;
     }
-    
+
     let c_str = unsafe { CStr::from_ptr(text) };
     let text_str = match c_str.to_str() {
         Ok(s) => s,
         Err(_) => return -1,
     };
-    
+
     if text_str.is_empty() {
         return 0;
     }
-    
-    unsafe {
-        match &TOKENIZER {
-            Some(bpe) => {
-                let tokens = bpe.encode_ordinary(text_str);
-                tokens.len() as c_int
-            }
-            None => -1,
-        }
-    }
+
+    let tokenizer = unsafe { &*tokenizer };
+    let tokens = tokenizer.bpe.encode_ordinary(text_str);
+    tokens.len() as c_int
 }
 
-/// Encode text to tokens
-/// Returns the number of tokens, fills the tokens array
-/// tokens_buffer must be pre-allocated with sufficient size
+/// Encode text to tokens using the given tokenizer.
+/// Returns the number of tokens, fills the tokens array.
+/// tokens_buffer must be pre-allocated with sufficient size.
 #[no_mangle]
 pub extern "C" fn tokenizer_encode(
+    tokenizer: *const Tokenizer,
     text: *const c_char,
     tokens_buffer: *mut c_int,
     buffer_size: size_t,
 ) -> c_int {
-    if text.is_null() || tokens_buffer.is_null() {
+    if tokenizer.is_null() || text.is_null() || tokens_buffer.is_null() {
         return -1;
     }
-    
+
     let c_str = unsafe { CStr::from_ptr(text) };
     let text_str = match c_str.to_str() {
         Ok(s) => s,
         Err(_) => return -1,
     };
-    
+
+    let tokenizer = unsafe { &*tokenizer };
+    let tokens = tokenizer.bpe.encode_ordinary(text_str);
+    let count = tokens.len().min(buffer_size);
+
+    #[cfg(debug_assertions)]
+    eprintln!(
+        "[tokenizer_encode] Input: {:?}, Generated {} tokens: {:?}",
+        text_str,
+        tokens.len(),
+        tokens
+    );
+
     unsafe {
-        match &TOKENIZER {
-            Some(bpe) => {
-                let tokens = bpe.encode_ordinary(text_str);
-                let count = tokens.len().min(buffer_size);
-                
-                #[cfg(debug_assertions)]
-                eprintln!("[tokenizer_encode] Input: {:?}, Generated {} tokens: {:?}", 
-                          text_str, tokens.len(), tokens);
-                
-                for (i, token) in tokens.iter().take(count).enumerate() {
-                    *tokens_buffer.add(i) = *token as c_int;
-                }
-                
-                tokens.len() as c_int
-            }
-            None => -1,
+        for (i, token) in tokens.iter().take(count).enumerate() {
+            *tokens_buffer.add(i) = *token as c_int;
         }
     }
+
+    tokens.len() as c_int
 }
 
-/// Decode tokens back to text
-/// Returns a null-terminated C string that must be freed by the caller
+/// Decode tokens back to text using the given tokenizer.
+/// Returns a null-terminated C string that must be freed by the caller.
 #[no_mangle]
-pub extern "C" fn tokenizer_decode(tokens: *const c_int, token_count: size_t) -> *mut c_char {
-    if tokens.is_null() || token_count == 0 {
+pub extern "C" fn tokenizer_decode(
+    tokenizer: *const Tokenizer,
+    tokens: *const c_int,
+    token_count: size_t,
+) -> *mut c_char {
+    if tokenizer.is_null() || tokens.is_null() || token_count == 0 {
         return ptr::null_mut();
     }
-    
-    unsafe {
-        match &TOKENIZER {
-            Some(bpe) => {
-                let tokens_slice = std::slice::from_raw_parts(tokens, token_count);
-                let tokens_vec: Vec<u32> = tokens_slice.iter().map(|&t| t as u32).collect();
-                
-                #[cfg(debug_assertions)]
-                eprintln!("[tokenizer_decode] Decoding {} tokens: {:?}", token_count, tokens_vec);
-                
-                match bpe.decode(tokens_vec) {
-                    Ok(text) => {
-                        #[cfg(debug_assertions)]
-                        eprintln!("[tokenizer_decode] Decoded text: {:?} (len: {})", text, text.len());
-                        
-                        match CString::new(text) {
-                            Ok(c_string) => c_string.into_raw(),
-                            Err(e) => {
-                                #[cfg(debug_assertions)]
-                                eprintln!("[tokenizer_decode] CString error: {:?}", e);
-                                ptr::null_mut()
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        #[cfg(debug_assertions)]
-                        eprintln!("[tokenizer_decode] Decode error: {:?}", e);
-                        ptr::null_mut()
-                    }
+
+    let tokenizer = unsafe { &*tokenizer };
+    let tokens_slice = unsafe { std::slice::from_raw_parts(tokens, token_count) };
+    let tokens_vec: Vec<u32> = tokens_slice.iter().map(|&t| t as u32).collect();
+
+    #[cfg(debug_assertions)]
+    eprintln!("[tokenizer_decode] Decoding {} tokens: {:?}", token_count, tokens_vec);
+
+    match tokenizer.bpe.decode(tokens_vec) {
+        Ok(text) => {
+            #[cfg(debug_assertions)]
+            eprintln!("[tokenizer_decode] Decoded text: {:?} (len: {})", text, text.len());
+
+            match CString::new(text) {
+                Ok(c_string) => c_string.into_raw(),
+                Err(e) => {
+                    #[cfg(debug_assertions)]
+                    eprintln!("[tokenizer_decode] CString error: {:?}", e);
+                    ptr::null_mut()
                 }
             }
-            None => ptr::null_mut(),
+        }
+        Err(e) => {
+            #[cfg(debug_assertions)]
+            eprintln!("[tokenizer_decode] Decode error: {:?}", e);
+            ptr::null_mut()
         }
     }
 }
 
-/// Free a string returned by tokenizer_decode
+/// Free a string returned by `tokenizer_decode` or `tokenizer_truncate_to_budget`.
 #[no_mangle]
 pub extern "C" fn tokenizer_free_string(s: *mut c_char) {
     if !s.is_null() {
@@ -154,44 +178,555 @@ pub extern "C" fn tokenizer_free_string(s: *mut c_char) {
     }
 }
 
-/// Check if the tokenizer is initialized
+/// Return how many tokens of `context_window` would be left after encoding
+/// `text` with the given tokenizer. The result is negative when `text`
+/// alone would overflow the window, so callers can treat it as a budget
+/// check rather than just a size.
+/// Returns `c_int::MIN` on error (null handle, null/invalid text).
+#[no_mangle]
+pub extern "C" fn tokenizer_remaining_tokens(
+    tokenizer: *const Tokenizer,
+    text: *const c_char,
+    context_window: c_int,
+) -> c_int {
+    if tokenizer.is_null() || text.is_null() {
+        return c_int::MIN;
+    }
+
+    let c_str = unsafe { CStr::from_ptr(text) };
+    let text_str = match c_str.to_str() {
+        Ok(s) => s,
+        Err(_) => return c_int::MIN,
+    };
+
+    let tokenizer = unsafe { &*tokenizer };
+    let used = tokenizer.bpe.encode_ordinary(text_str).len() as c_int;
+    context_window - used
+}
+
+/// Encode `text`, keep only the first `max_tokens` tokens, and decode that
+/// prefix back into a valid UTF-8 C string.
+///
+/// Because a token boundary need not fall on a character boundary, the
+/// prefix is shrunk one token at a time until it decodes successfully, so
+/// the result never ends with a truncated multi-byte character.
+/// Returns null on error; the returned string must be freed with
+/// `tokenizer_free_string`.
+#[no_mangle]
+pub extern "C" fn tokenizer_truncate_to_budget(
+    tokenizer: *const Tokenizer,
+    text: *const c_char,
+    max_tokens: size_t,
+) -> *mut c_char {
+    if tokenizer.is_null() || text.is_null() {
+        return ptr::null_mut();
+    }
+
+    let c_str = unsafe { CStr::from_ptr(text) };
+    let text_str = match c_str.to_str() {
+        Ok(s) => s,
+        Err(_) => return ptr::null_mut(),
+    };
+
+    let tokenizer = unsafe { &*tokenizer };
+    let tokens = tokenizer.bpe.encode_ordinary(text_str);
+    let mut prefix_len = tokens.len().min(max_tokens);
+
+    loop {
+        let prefix: Vec<u32> = tokens[..prefix_len].to_vec();
+        match tokenizer.bpe.decode(prefix) {
+            Ok(decoded) => match CString::new(decoded) {
+                Ok(c_string) => return c_string.into_raw(),
+                Err(_) => return ptr::null_mut(),
+            },
+            Err(_) if prefix_len > 0 => prefix_len -= 1,
+            Err(_) => return ptr::null_mut(),
+        }
+    }
+}
+
+/// Decode `tokens[start..end]`, shrinking `end` until the slice lands on a
+/// character boundary, and return `(byte length of the decoded slice, the
+/// actual end index used)`.
+///
+/// Each token maps to a fixed byte sequence regardless of its neighbors, so
+/// decoding a sub-range gives exactly the bytes that range contributes to a
+/// full decode - this lets callers accumulate offsets incrementally instead
+/// of re-decoding the whole prefix from the start on every call. The actual
+/// end index is returned (rather than assumed to be `end`) so that any
+/// tokens shrunk off the end are picked up by the next call instead of
+/// being dropped permanently.
+fn decode_range_byte_len(tokenizer: &Tokenizer, tokens: &[u32], start: usize, end: usize) -> (usize, usize) {
+    let mut e = end;
+    loop {
+        match tokenizer.bpe.decode(tokens[start..e].to_vec()) {
+            Ok(decoded) => return (decoded.len(), e),
+            Err(_) if e > start => e -= 1,
+            Err(_) => return (0, start),
+        }
+    }
+}
+
+/// Split `text` into chunks of at most `max_tokens_per_chunk` tokens, with
+/// consecutive chunks sharing `overlap_tokens` tokens of context.
+///
+/// Writes the byte offset where each chunk starts into `out_offsets` (up to
+/// `out_count` entries) and returns the total number of chunks, so the
+/// caller can tell the buffer was too small and retry with a bigger one.
+/// The last chunk absorbs whatever remains even if shorter than the limit.
+/// Returns -1 on error, including when `overlap_tokens >= max_tokens_per_chunk`.
+#[no_mangle]
+pub extern "C" fn tokenizer_chunk(
+    tokenizer: *const Tokenizer,
+    text: *const c_char,
+    max_tokens_per_chunk: size_t,
+    overlap_tokens: size_t,
+    out_offsets: *mut size_t,
+    out_count: size_t,
+) -> c_int {
+    if tokenizer.is_null() || text.is_null() || max_tokens_per_chunk == 0 {
+        return -1;
+    }
+    if overlap_tokens >= max_tokens_per_chunk {
+        return -1;
+    }
+
+    let c_str = unsafe { CStr::from_ptr(text) };
+    let text_str = match c_str.to_str() {
+        Ok(s) => s,
+        Err(_) => return -1,
+    };
+
+    let tokenizer = unsafe { &*tokenizer };
+    let tokens = tokenizer.bpe.encode_ordinary(text_str);
+    let stride = max_tokens_per_chunk - overlap_tokens;
+
+    let mut offsets = Vec::new();
+    let mut start_token = 0usize;
+    let mut prev_start_token = 0usize;
+    let mut cumulative_bytes = 0usize;
+    loop {
+        if start_token > prev_start_token {
+            let (len, actual_end) = decode_range_byte_len(tokenizer, &tokens, prev_start_token, start_token);
+            cumulative_bytes += len;
+            prev_start_token = actual_end;
+        }
+        offsets.push(cumulative_bytes);
+
+        if start_token + max_tokens_per_chunk >= tokens.len() {
+            break;
+        }
+        start_token += stride;
+    }
+
+    if !out_offsets.is_null() {
+        let write_count = offsets.len().min(out_count);
+        unsafe {
+            for (i, offset) in offsets.iter().take(write_count).enumerate() {
+                *out_offsets.add(i) = *offset;
+            }
+        }
+    }
+
+    offsets.len() as c_int
+}
+
+/// Read `allowed_count` null-terminated C strings out of `allowed_special`.
+/// Returns `None` if any entry is null or not valid UTF-8.
+fn parse_allowed_special(
+    allowed_special: *const *const c_char,
+    allowed_count: size_t,
+) -> Option<Vec<String>> {
+    if allowed_count == 0 {
+        return Some(Vec::new());
+    }
+    if allowed_special.is_null() {
+        return None;
+    }
+
+    let mut special = Vec::with_capacity(allowed_count);
+    for i in 0..allowed_count {
+        let entry = unsafe { *allowed_special.add(i) };
+        if entry.is_null() {
+            return None;
+        }
+        let s = unsafe { CStr::from_ptr(entry) }.to_str().ok()?;
+        special.push(s.to_owned());
+    }
+    Some(special)
+}
+
+/// Encode `text` with special tokens enabled, using `encode` instead of
+/// `encode_ordinary` so that strings like `<|endoftext|>` listed in
+/// `allowed_special` are tokenized as the model's own control tokens
+/// rather than as ordinary text. Any special token not in the allowlist is
+/// still treated as ordinary text.
+/// Returns the number of tokens, fills `tokens_buffer`, or -1 on error.
 #[no_mangle]
-pub extern "C" fn tokenizer_is_ready() -> c_int {
+pub extern "C" fn tokenizer_encode_with_special(
+    tokenizer: *const Tokenizer,
+    text: *const c_char,
+    allowed_special: *const *const c_char,
+    allowed_count: size_t,
+    tokens_buffer: *mut c_int,
+    buffer_size: size_t,
+) -> c_int {
+    if tokenizer.is_null() || text.is_null() || tokens_buffer.is_null() {
+        return -1;
+    }
+
+    let c_str = unsafe { CStr::from_ptr(text) };
+    let text_str = match c_str.to_str() {
+        Ok(s) => s,
+        Err(_) => return -1,
+    };
+
+    let special = match parse_allowed_special(allowed_special, allowed_count) {
+        Some(special) => special,
+        None => return -1,
+    };
+    let special_set: HashSet<&str> = special.iter().map(|s| s.as_str()).collect();
+
+    let tokenizer = unsafe { &*tokenizer };
+    let tokens = tokenizer.bpe.encode(text_str, special_set);
+    let count = tokens.len().min(buffer_size);
+
     unsafe {
-        if TOKENIZER.is_some() {
-            1
+        for (i, token) in tokens.iter().take(count).enumerate() {
+            *tokens_buffer.add(i) = *token as c_int;
+        }
+    }
+
+    tokens.len() as c_int
+}
+
+/// Count tokens in `text` the same way `tokenizer_encode_with_special` does,
+/// without needing a pre-sized output buffer. Returns -1 on error.
+#[no_mangle]
+pub extern "C" fn tokenizer_count_tokens_with_special(
+    tokenizer: *const Tokenizer,
+    text: *const c_char,
+    allowed_special: *const *const c_char,
+    allowed_count: size_t,
+) -> c_int {
+    if tokenizer.is_null() || text.is_null() {
+        return -1;
+    }
+
+    let c_str = unsafe { CStr::from_ptr(text) };
+    let text_str = match c_str.to_str() {
+        Ok(s) => s,
+        Err(_) => return -1,
+    };
+
+    let special = match parse_allowed_special(allowed_special, allowed_count) {
+        Some(special) => special,
+        None => return -1,
+    };
+    let special_set: HashSet<&str> = special.iter().map(|s| s.as_str()).collect();
+
+    let tokenizer = unsafe { &*tokenizer };
+    let tokens = tokenizer.bpe.encode(text_str, special_set);
+    tokens.len() as c_int
+}
+
+/// Estimate the token count of `text` without loading any BPE vocabulary.
+///
+/// Splits on Unicode word boundaries: each run of alphanumerics costs
+/// roughly one token per 4 bytes (rounded up), each punctuation/symbol
+/// cluster costs one token, and whitespace runs are free. This is far
+/// cheaper than `tokenizer_count_tokens` and is meant for pre-filtering or
+/// UI estimates over large file sets, not for anything that needs to match
+/// a model's exact tokenization.
+/// Returns -1 if `text` is null or not valid UTF-8.
+#[no_mangle]
+pub extern "C" fn tokenizer_estimate_tokens(text: *const c_char) -> c_int {
+    if text.is_null() {
+        return -1;
+    }
+
+    let c_str = unsafe { CStr::from_ptr(text) };
+    let text_str = match c_str.to_str() {
+        Ok(s) => s,
+        Err(_) => return -1,
+    };
+
+    let mut estimate: usize = 0;
+    for word in text_str.split_word_bounds() {
+        if word.chars().all(|c| c.is_whitespace()) {
+            continue;
+        }
+        if word.chars().any(|c| c.is_alphanumeric()) {
+            // `split_word_bounds` keeps `_` glued to neighboring alphanumerics
+            // (e.g. `my_snake_case_var`), so require only one alphanumeric
+            // char rather than all of them, or identifiers like that would
+            // fall through to the flat 1-token branch below.
+            estimate += (word.len() + 3) / 4;
         } else {
-            0
+            estimate += 1;
         }
     }
+
+    estimate as c_int
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::ffi::CString;
-    
+
+    fn make_tokenizer(encoding: &str) -> *mut Tokenizer {
+        let name = CString::new(encoding).unwrap();
+        tokenizer_create(name.as_ptr())
+    }
+
+    #[test]
+    fn test_create_and_destroy() {
+        let tokenizer = make_tokenizer("cl100k_base");
+        assert!(!tokenizer.is_null());
+        tokenizer_destroy(tokenizer);
+    }
+
     #[test]
-    fn test_initialization() {
-        assert_eq!(tokenizer_initialize(), 0);
-        assert_eq!(tokenizer_is_ready(), 1);
+    fn test_create_unknown_encoding() {
+        let name = CString::new("not_a_real_encoding").unwrap();
+        let tokenizer = tokenizer_create(name.as_ptr());
+        assert!(tokenizer.is_null());
     }
-    
+
     #[test]
     fn test_count_tokens() {
-        tokenizer_initialize();
-        
+        let tokenizer = make_tokenizer("cl100k_base");
+
         let text = CString::new("Hello, world!").unwrap();
-        let count = tokenizer_count_tokens(text.as_ptr());
+        let count = tokenizer_count_tokens(tokenizer, text.as_ptr());
         assert_eq!(count, 3); // "Hello", ", world", "!"
+
+        tokenizer_destroy(tokenizer);
     }
-    
+
     #[test]
     fn test_empty_string() {
-        tokenizer_initialize();
-        
+        let tokenizer = make_tokenizer("cl100k_base");
+
         let text = CString::new("").unwrap();
-        let count = tokenizer_count_tokens(text.as_ptr());
+        let count = tokenizer_count_tokens(tokenizer, text.as_ptr());
         assert_eq!(count, 0);
+
+        tokenizer_destroy(tokenizer);
+    }
+
+    #[test]
+    fn test_multiple_tokenizers_independent() {
+        let cl100k = make_tokenizer("cl100k_base");
+        let o200k = make_tokenizer("o200k_base");
+
+        let text = CString::new("Hello, world!").unwrap();
+        assert!(tokenizer_count_tokens(cl100k, text.as_ptr()) > 0);
+        assert!(tokenizer_count_tokens(o200k, text.as_ptr()) > 0);
+
+        tokenizer_destroy(cl100k);
+        tokenizer_destroy(o200k);
+    }
+
+    #[test]
+    fn test_remaining_tokens() {
+        let tokenizer = make_tokenizer("cl100k_base");
+
+        let text = CString::new("Hello, world!").unwrap();
+        let remaining = tokenizer_remaining_tokens(tokenizer, text.as_ptr(), 10);
+        assert_eq!(remaining, 10 - 4); // "Hello", ",", " world", "!"
+
+        let over_budget = tokenizer_remaining_tokens(tokenizer, text.as_ptr(), 1);
+        assert!(over_budget < 0);
+
+        tokenizer_destroy(tokenizer);
+    }
+
+    #[test]
+    fn test_truncate_to_budget_never_splits_a_multibyte_char() {
+        let tokenizer = make_tokenizer("cl100k_base");
+
+        // Repeated multi-byte characters are prone to landing a token
+        // boundary in the middle of one; try every budget from 1 token up
+        // so any truncation-induced UTF-8 split would be caught.
+        let text_str = "\u{1F600}\u{1F601}\u{1F602}\u{1F603}\u{1F604}\u{1F605}aa bb cc";
+        let text = CString::new(text_str).unwrap();
+        let total = tokenizer_count_tokens(tokenizer, text.as_ptr()) as size_t;
+
+        for max_tokens in 1..=total {
+            let truncated_ptr = tokenizer_truncate_to_budget(tokenizer, text.as_ptr(), max_tokens);
+            assert!(!truncated_ptr.is_null());
+
+            let truncated = unsafe { CStr::from_ptr(truncated_ptr) }
+                .to_str()
+                .expect("truncated output must be valid UTF-8")
+                .to_owned();
+            assert!(text_str.starts_with(&truncated));
+
+            tokenizer_free_string(truncated_ptr);
+        }
+
+        tokenizer_destroy(tokenizer);
+    }
+
+    #[test]
+    fn test_chunk_offsets_land_on_char_boundaries_and_cover_the_whole_text() {
+        let tokenizer = make_tokenizer("cl100k_base");
+
+        let text_str = "\u{1F600}\u{1F601}\u{1F602}\u{1F603}\u{1F604}\u{1F605}\u{1F606}\u{1F607}";
+        let text = CString::new(text_str).unwrap();
+
+        let mut offsets = vec![0usize; 64];
+        let count = tokenizer_chunk(tokenizer, text.as_ptr(), 2, 0, offsets.as_mut_ptr(), offsets.len());
+        assert!(count > 1);
+
+        // The first chunk starts at the beginning and the last one starts
+        // before the end, so together the chunks cover the whole text.
+        assert_eq!(offsets[0], 0);
+        assert!(offsets[count as usize - 1] < text_str.len());
+
+        for &offset in offsets.iter().take(count as usize) {
+            assert!(text_str.is_char_boundary(offset));
+        }
+
+        tokenizer_destroy(tokenizer);
+    }
+
+    #[test]
+    fn test_chunk_overlap_shares_tokens_between_consecutive_chunks() {
+        let tokenizer = make_tokenizer("cl100k_base");
+
+        let text_str = "one two three four five six seven eight nine ten";
+        let text = CString::new(text_str).unwrap();
+
+        let mut offsets = vec![0usize; 64];
+        let count = tokenizer_chunk(tokenizer, text.as_ptr(), 4, 2, offsets.as_mut_ptr(), offsets.len());
+        assert!(count >= 2);
+
+        // With overlap_tokens == 2 the stride is 2, so the second chunk
+        // should start strictly before the end of the first chunk's 4-token
+        // window (where a non-overlapping split starts its second chunk),
+        // not merely after the first chunk's start.
+        let mut plain = vec![0usize; 64];
+        let plain_count = tokenizer_chunk(tokenizer, text.as_ptr(), 4, 0, plain.as_mut_ptr(), plain.len());
+        assert!(plain_count >= 2);
+        assert!(offsets[1] < plain[1]);
+
+        for pair in offsets[..count as usize].windows(2) {
+            assert!(pair[1] > pair[0]);
+        }
+
+        tokenizer_destroy(tokenizer);
+    }
+
+    #[test]
+    fn test_chunk_overlap_with_multibyte_chars_offsets_strictly_increase() {
+        let tokenizer = make_tokenizer("cl100k_base");
+
+        // Overlap and multi-byte characters together exercise the
+        // incremental-decode bookkeeping: a shrunk boundary must carry its
+        // leftover tokens into the next increment rather than dropping them,
+        // so no two reported offsets may repeat.
+        let text_str = "ab\u{1F600}cd\u{1F601}ef\u{1F602}gh\u{1F603}ij\u{1F604}kl\u{1F605}mn\u{1F606}op";
+        let text = CString::new(text_str).unwrap();
+
+        let mut offsets = vec![0usize; 64];
+        let count = tokenizer_chunk(tokenizer, text.as_ptr(), 3, 1, offsets.as_mut_ptr(), offsets.len());
+        assert!(count >= 2);
+
+        let used = &offsets[..count as usize];
+        for pair in used.windows(2) {
+            assert!(pair[1] > pair[0], "offsets must strictly increase: {:?}", used);
+        }
+        for &offset in used {
+            assert!(text_str.is_char_boundary(offset));
+        }
+
+        tokenizer_destroy(tokenizer);
+    }
+
+    #[test]
+    fn test_chunk_rejects_overlap_not_smaller_than_chunk_size() {
+        let tokenizer = make_tokenizer("cl100k_base");
+
+        let text = CString::new("hello world").unwrap();
+        let mut offsets = vec![0usize; 8];
+        let count = tokenizer_chunk(tokenizer, text.as_ptr(), 4, 4, offsets.as_mut_ptr(), offsets.len());
+        assert_eq!(count, -1);
+
+        tokenizer_destroy(tokenizer);
+    }
+
+    #[test]
+    fn test_estimate_tokens_scales_with_identifier_length() {
+        let short_ident = CString::new("a_b").unwrap();
+        let long_ident = CString::new("my_very_long_snake_case_variable_name").unwrap();
+
+        let short_estimate = tokenizer_estimate_tokens(short_ident.as_ptr());
+        let long_estimate = tokenizer_estimate_tokens(long_ident.as_ptr());
+
+        // A long snake_case identifier must cost noticeably more than a
+        // short one - it must not be flattened to a single token just
+        // because `_` breaks the `is_alphanumeric` run.
+        assert!(long_estimate > short_estimate + 5);
+    }
+
+    #[test]
+    fn test_estimate_tokens_whitespace_is_free_and_punctuation_counts() {
+        let text = CString::new("foo   bar, baz!").unwrap();
+        let estimate = tokenizer_estimate_tokens(text.as_ptr());
+        // "foo" + "bar" + "," + " " (free) + "baz" + "!" -> at least 5 units,
+        // none of them charged for the whitespace runs.
+        assert!(estimate >= 5);
+    }
+
+    #[test]
+    fn test_estimate_tokens_null_is_error() {
+        assert_eq!(tokenizer_estimate_tokens(ptr::null()), -1);
+    }
+
+    #[test]
+    fn test_special_token_is_collapsed_only_when_allowed() {
+        let tokenizer = make_tokenizer("cl100k_base");
+        let text = CString::new("<|endoftext|>").unwrap();
+
+        // Without an allowlist, `<|endoftext|>` is ordinary text and splits
+        // into several BPE tokens.
+        let ordinary_count = tokenizer_count_tokens(tokenizer, text.as_ptr());
+        assert!(ordinary_count > 1);
+
+        // With it on the allowlist, it collapses to the model's single
+        // control token.
+        let special = CString::new("<|endoftext|>").unwrap();
+        let allowed = [special.as_ptr()];
+        let special_count =
+            tokenizer_count_tokens_with_special(tokenizer, text.as_ptr(), allowed.as_ptr(), allowed.len());
+        assert_eq!(special_count, 1);
+
+        let mut buffer = [0 as c_int; 4];
+        let encoded_count = tokenizer_encode_with_special(
+            tokenizer,
+            text.as_ptr(),
+            allowed.as_ptr(),
+            allowed.len(),
+            buffer.as_mut_ptr(),
+            buffer.len(),
+        );
+        assert_eq!(encoded_count, 1);
+
+        tokenizer_destroy(tokenizer);
+    }
+
+    #[test]
+    fn test_encode_with_special_rejects_null_allowlist_with_nonzero_count() {
+        let tokenizer = make_tokenizer("cl100k_base");
+        let text = CString::new("hello").unwrap();
+        let mut buffer = [0 as c_int; 4];
+
+        let count = tokenizer_encode_with_special(tokenizer, text.as_ptr(), ptr::null(), 1, buffer.as_mut_ptr(), buffer.len());
+        assert_eq!(count, -1);
+
+        tokenizer_destroy(tokenizer);
     }
 }